@@ -1,12 +1,32 @@
 use std::vec::FromVec;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
 use module::*;
 use scoped_map::ScopedMap;
 use interner::*;
 
-#[deriving(Eq, TotalEq, Hash, Clone, Show)]
+#[deriving(Clone, Show)]
 pub struct Name {
     pub name: InternedStr,
-    pub uid: uint
+    pub uid: uint,
+    pub module: Option<InternedStr>
+}
+
+//`module` is provenance for where a reference was written, not part of a name's
+//identity: the same global compares equal whether it is seen qualified or not,
+//or at its own definition site, as long as the `uid` (and interned text) match.
+impl Eq for Name {
+    fn eq(&self, other: &Name) -> bool {
+        self.uid == other.uid && self.name == other.name
+    }
+}
+impl TotalEq for Name { }
+
+impl<S: Writer> Hash<S> for Name {
+    fn hash(&self, state: &mut S) {
+        self.name.hash(state);
+        self.uid.hash(state);
+    }
 }
 
 impl Str for Name {
@@ -18,22 +38,140 @@ impl Str for Name {
     }
 }
 
+#[deriving(Clone, Show)]
+pub enum RenameError {
+    UnboundVariable(InternedStr, Location),
+    ConstructorArityMismatch {
+        name: InternedStr,
+        expected: uint,
+        found: uint,
+        location: Location
+    }
+}
+
+#[deriving(Clone, Show)]
+pub struct ShadowWarning {
+    pub name: InternedStr,
+    pub outer: Name,
+    pub location: Location
+}
+
+//Uid handed back for a name the renamer could not resolve at all. Deliberately
+//not `0`: this renamer (like the original) still uses `uid: 0` to mean "global
+//resolved by its text, not a concrete slot" (see `rename_expr`'s caller-supplied
+//globals), so reusing it here would make a failed lookup indistinguishable from
+//that case. `make_unique`/`collect_global_env` never hand out this value.
+pub static UNRESOLVED_UID: uint = 0xffffffff;
+
 struct Renamer {
     uniques: ScopedMap<InternedStr, Name>,
-    unique_id: uint
+    unique_id: uint,
+    //Keyed by (module, name) so two modules exporting the same symbol name stay
+    //distinct; an unqualified reference looks itself up under `(module_name, s)`.
+    globals: HashMap<(InternedStr, InternedStr), Name>,
+    //Flat fallback over every module's exports, keyed by symbol alone. Used when
+    //an unqualified reference isn't declared in the current module: real Haskell
+    //programs rely on import lists to disambiguate a clash here, which this
+    //compiler doesn't model yet, so the last module to declare a given name wins.
+    globals_by_name: HashMap<InternedStr, Name>,
+    module_name: InternedStr,
+    //Declared arity of every data constructor in the program, keyed by its
+    //unqualified name; checked against `ConstructorPattern`'s argument count.
+    //`rename_modules` seeds this from every module before renaming any of them,
+    //so it also covers constructors declared in a different module.
+    ctor_arity: HashMap<InternedStr, uint>,
+    imports: HashSet<InternedStr>,
+    errors: Vec<RenameError>,
+    warnings: Vec<ShadowWarning>
 }
 
 impl Renamer {
 
+    //Renames the declarations of a single module. Callers are responsible for
+    //seeding `globals`/`imports` beforehand: `rename_module` seeds `globals` from
+    //this module alone and `imports` from its caller's list, `rename_modules`
+    //seeds both from every module in the program.
+    fn rename_module_body(&mut self, module: Module<InternedStr>) -> Module<Name> {
+        let Module {
+            name: name,
+            classes : classes,
+            dataDefinitions: data_definitions,
+            typeDeclarations: typeDeclarations,
+            bindings : bindings,
+            instances: instances
+        } = module;
+
+        let data_definitions2 : Vec<DataDefinition<Name>> = data_definitions.move_iter().map(|data| {
+            let DataDefinition {
+                constructors : ctors,
+                typ : typ,
+                parameters : parameters
+            } = data;
+            let c: Vec<Constructor<Name>> = ctors.move_iter().map(|ctor| {
+                let Constructor {
+                    name : name,
+                    typ : typ,
+                    tag : tag,
+                    arity : arity
+                } = ctor;
+                self.ctor_arity.insert(name.clone(), arity);
+                //Constructors were already allocated a real uid in `collect_global_env`
+                //so uses in expression and pattern position agree with the declaration.
+                let key = (self.module_name.clone(), name.clone());
+                let resolved = self.globals.find(&key).map(|n| n.clone())
+                    .expect(format!("Renamer: constructor {} missing from global environment", name));
+                Constructor {
+                    name : resolved,
+                    typ : typ,
+                    tag : tag,
+                    arity : arity
+                }
+            }).collect();
+
+            DataDefinition {
+                typ : typ,
+                parameters : parameters,
+                constructors : FromVec::from_vec(c)
+            }
+        }).collect();
+
+        let instances2: Vec<Instance<Name>> = instances.move_iter().map(|instance| {
+            let Instance {
+                bindings : bindings,
+                constraints : constraints,
+                typ : typ,
+                classname : classname
+            } = instance;
+            Instance {
+                bindings : FromVec::<Binding<Name>>::from_vec(bindings.move_iter().map(|b| self.rename_binding(b)).collect()),
+                constraints : constraints,
+                typ : typ,
+                classname : classname
+            }
+        }).collect();
+
+        let bindings2 : Vec<Binding<Name>> = bindings.move_iter().map(|b| self.rename_binding(b)).collect();
+
+        Module {
+            name: self.make_unique(name),
+            classes : classes,
+            dataDefinitions: FromVec::from_vec(data_definitions2),
+            typeDeclarations: typeDeclarations,
+            bindings : FromVec::from_vec(bindings2),
+            instances: FromVec::from_vec(instances2)
+        }
+    }
+
     fn rename_bindings(&mut self, bindings: ~[Binding<InternedStr>]) -> ~[Binding<Name>] {
         //Add all bindings in the scope
         for bind in bindings.iter() {
-            self.make_unique(bind.name.clone());
+            self.bind_unique(bind.name.clone(), bind.expression.location);
         }
         FromVec::<Binding<Name>>::from_vec(bindings.move_iter().map(|binding| {
             let Binding { name: name, expression: expression, typeDecl: typeDecl, arity: arity  } = binding;
+            let location = expression.location;
             let n = self.uniques.find(&name).map(|u| u.clone())
-                .expect(format!("Error: lambda_lift: Undefined variable {}", name));
+                .unwrap_or_else(|| self.report_error(name.clone(), location));
             Binding {
                 name: n,
                 expression: self.rename(expression),
@@ -50,11 +188,11 @@ impl Renamer {
             Rational(r) => Rational(r),
             String(s) => String(s),
             Char(c) => Char(c),
-            Identifier(i) => Identifier(self.get_name(i)),
+            Identifier(i) => Identifier(self.get_name(i, location)),
             Apply(func, arg) => Apply(box self.rename(*func), box self.rename(*arg)),
             Lambda(arg, body) => {
                 self.uniques.enter_scope();
-                let l = Lambda(self.make_unique(arg), box self.rename(*body));
+                let l = Lambda(self.bind_unique(arg, location), box self.rename(*body));
                 self.uniques.exit_scope();
                 l
             }
@@ -70,7 +208,7 @@ impl Renamer {
                     |Alternative { pattern: Located { location: loc, node: pattern }, expression: expression }| {
                     self.uniques.enter_scope();
                     let a = Alternative {
-                        pattern: Located { location: loc, node: self.rename_pattern(pattern) },
+                        pattern: Located { location: loc, node: self.rename_pattern(pattern, loc) },
                         expression: self.rename(expression)
                     };
                     self.uniques.exit_scope();
@@ -85,7 +223,7 @@ impl Renamer {
                         DoLet(bs) => DoLet(self.rename_bindings(bs)),
                         DoBind(pattern, expr) => {
                             let Located { location: location, node: node } = pattern;
-                            let loc = Located { location: location, node: self.rename_pattern(node) };
+                            let loc = Located { location: location, node: self.rename_pattern(node, location) };
                             DoBind(loc, self.rename(expr))
                         }
                     }
@@ -98,28 +236,84 @@ impl Renamer {
         t
     }
 
-    fn rename_pattern(&mut self, pattern: Pattern<InternedStr>) -> Pattern<Name> {
+    fn rename_pattern(&mut self, pattern: Pattern<InternedStr>, location: Location) -> Pattern<Name> {
         match pattern {
             NumberPattern(i) => NumberPattern(i),
             ConstructorPattern(s, ps) => {
-                let ps2: Vec<Pattern<Name>> = ps.move_iter().map(|p| self.rename_pattern(p)).collect();
-                ConstructorPattern(Name { name: s, uid: 0}, FromVec::from_vec(ps2))
+                let ps2: Vec<Pattern<Name>> = ps.move_iter().map(|p| self.rename_pattern(p, location)).collect();
+                let arity = self.ctor_arity.find(&ctor_key(&s)).map(|a| *a);
+                let name = self.get_name(s, location);
+                match arity {
+                    Some(expected) if expected != ps2.len() => {
+                        self.errors.push(ConstructorArityMismatch {
+                            name: name.name.clone(),
+                            expected: expected,
+                            found: ps2.len(),
+                            location: location
+                        });
+                    }
+                    _ => ()
+                }
+                ConstructorPattern(name, FromVec::from_vec(ps2))
             }
-            IdentifierPattern(s) => IdentifierPattern(self.make_unique(s)),
+            IdentifierPattern(s) => IdentifierPattern(self.bind_unique(s, location)),
             WildCardPattern => WildCardPattern
         }
     }
-    fn get_name(&self, s: InternedStr) -> Name {
+
+    //Resolves a surface identifier to a `Name`, splitting off a module qualifier
+    //(`Prelude.map`) first if one is present. This is the single place a surface
+    //name is bound to a (module, uid) pair; unqualified names still go through the
+    //local `ScopedMap`/globals lookup as before.
+    fn get_name(&mut self, s: InternedStr, location: Location) -> Name {
+        match qualifier(&s) {
+            Some((module, symbol)) => self.get_qualified_name(module, symbol, location),
+            None => self.get_unqualified_name(s, location)
+        }
+    }
+
+    fn get_unqualified_name(&mut self, s: InternedStr, location: Location) -> Name {
         match self.uniques.find(&s) {
-            Some(&Name { uid: uid, .. }) => Name { name: s, uid: uid },
-            None => Name { name: s, uid: 0 }//If the variable is not found in variables it is a global variable
+            Some(&Name { uid: uid, .. }) => Name { name: s, uid: uid, module: None },
+            None => match resolve_global(&self.module_name, &s, &self.globals, &self.globals_by_name) {
+                Some(global) => global,
+                None => self.report_error(s, location)
+            }
         }
     }
 
+    fn get_qualified_name(&mut self, module: InternedStr, symbol: InternedStr, location: Location) -> Name {
+        if !self.imports.contains(&module) {
+            return self.report_error(symbol, location);
+        }
+        let key = (module.clone(), symbol.clone());
+        match self.globals.find(&key) {
+            Some(global) => Name { name: global.name.clone(), uid: global.uid, module: Some(module) },
+            //Known import, but `globals` holds no such symbol for it; still worth
+            //flagging instead of silently resolving to the wrong module. Under
+            //`rename_module`, `globals` holds only the current module's own exports,
+            //so every qualified reference into another module is reported here.
+            None => self.report_error(symbol, location)
+        }
+    }
+
+    fn report_error(&mut self, name: InternedStr, location: Location) -> Name {
+        self.errors.push(UnboundVariable(name.clone(), location));
+        Name { name: name, uid: UNRESOLVED_UID, module: None }
+    }
+
+    //Top-level bindings were already allocated a real uid in `collect_global_env`;
+    //reuse it here so the definition shares an id with every reference
+    //`get_unqualified_name`/`get_qualified_name` resolves to it. Instance bindings
+    //are not collected there: one takes the uid of the same-named class method
+    //when that class is declared in this module, and `UNRESOLVED_UID` otherwise.
     fn rename_binding(&mut self, binding: Binding<InternedStr>) -> Binding<Name> {
         let Binding { name: name, expression: expression, typeDecl: td, arity: a } = binding;
+        let key = (self.module_name.clone(), name.clone());
+        let resolved = self.globals.find(&key).map(|n| n.clone())
+            .unwrap_or_else(|| Name { name: name.clone(), uid: UNRESOLVED_UID, module: None });
         Binding {
-            name: Name { name: name, uid: 0 },
+            name: resolved,
             expression: self.rename(expression),
             typeDecl: td,
             arity: a
@@ -129,79 +323,256 @@ impl Renamer {
 
     fn make_unique(&mut self, name: InternedStr) -> Name {
         self.unique_id += 1;
-        let u = Name { name: name.clone(), uid: self.unique_id};
+        let u = Name { name: name.clone(), uid: self.unique_id, module: None };
         self.uniques.insert(name, u.clone());
         u
     }
+
+    //Like `make_unique`, but first checks whether `name` is already bound in an
+    //enclosing scope and records a `ShadowWarning` if so. Used at every binding
+    //site that has a location to blame (lambda/let/case bindings); the module's
+    //own top-level name has no enclosing scope to shadow, so it goes straight
+    //through `make_unique`.
+    fn bind_unique(&mut self, name: InternedStr, location: Location) -> Name {
+        if self.uniques.in_outer_scope(&name) {
+            let outer = self.uniques.find(&name).map(|n| n.clone()).unwrap();
+            self.warnings.push(ShadowWarning { name: name.clone(), outer: outer, location: location });
+        }
+        self.make_unique(name)
+    }
 }
-pub fn rename_expr(expr: TypedExpr<InternedStr>) -> TypedExpr<Name> {
-    let mut renamer = Renamer { uniques: ScopedMap::new(), unique_id: 1 };
-    renamer.rename(expr)
-}
-
-pub fn rename_module(module: Module<InternedStr>) -> Module<Name> {
-    let mut renamer = Renamer { uniques: ScopedMap::new(), unique_id: 1 };
-    let Module {
-        name: name,
-        classes : classes,
-        dataDefinitions: data_definitions,
-        typeDeclarations: typeDeclarations,
-        bindings : bindings,
-        instances: instances
-    } = module;
-
-    let data_definitions2 : Vec<DataDefinition<Name>> = data_definitions.move_iter().map(|data| {
-        let DataDefinition {
-            constructors : ctors,
-            typ : typ,
-            parameters : parameters
-        } = data;
-        let c: Vec<Constructor<Name>> = ctors.move_iter().map(|ctor| {
-            let Constructor {
-                name : name,
-                typ : typ,
-                tag : tag,
-                arity : arity
-            } = ctor;
-            Constructor {
-                name : Name { name: name, uid: 0 },
-                typ : typ,
-                tag : tag,
-                arity : arity
+
+//Splits `Data.List.foldr` into module `Data.List` and symbol `foldr`. Returns
+//None for unqualified identifiers (no '.') as well as for anything that merely
+//contains a dot without looking like a qualified name, e.g. the compose
+//operator `.` itself, or a name starting/ending with '.' - those fall through
+//to `get_unqualified_name` instead of being misread as an empty module/symbol.
+fn qualifier(s: &InternedStr) -> Option<(InternedStr, InternedStr)> {
+    let text = s.as_slice();
+    match text.rfind('.') {
+        Some(i) if i > 0 && i + 1 < text.len() => {
+            let module = text.slice_to(i);
+            let symbol = text.slice_from(i + 1);
+            if is_module_name(module) {
+                Some((intern(module), intern(symbol)))
+            } else {
+                None
             }
-        }).collect();
+        }
+        _ => None
+    }
+}
+
+//Module-style qualifiers start with an uppercase letter, matching how this
+//compiler's own module names (and Haskell's) are written.
+fn is_module_name(text: &str) -> bool {
+    text.char_at(0).is_uppercase()
+}
+
+//Resolves `s` as the current module's own export first, falling back to any
+//module's export of the same symbol. Returns `None` instead of reporting an
+//error so callers can attach their own location-tagged diagnostic; kept free of
+//`Renamer` so it can be exercised directly without building a `Location`.
+fn resolve_global(module_name: &InternedStr, s: &InternedStr, globals: &HashMap<(InternedStr, InternedStr), Name>, globals_by_name: &HashMap<InternedStr, Name>) -> Option<Name> {
+    let key = (module_name.clone(), s.clone());
+    match globals.find(&key) {
+        Some(global) => Some(global.clone()),
+        None => globals_by_name.find(s).map(|global| global.clone())
+    }
+}
+
+//The unqualified part of a name, used to key `ctor_arity` regardless of whether
+//the pattern wrote the constructor qualified (`Data.Maybe.Just`) or not (`Just`).
+fn ctor_key(s: &InternedStr) -> InternedStr {
+    match qualifier(s) {
+        Some((_, symbol)) => symbol,
+        None => s.clone()
+    }
+}
 
-        DataDefinition {
-            typ : typ,
-            parameters : parameters,
-            constructors : FromVec::from_vec(c)
+//Records the declared arity of every data constructor in `module`, keyed by its
+//unqualified name, so `ConstructorPattern`s can be arity-checked even when the
+//pattern was written in a different module than the constructor's declaration.
+fn collect_ctor_arity(module: &Module<InternedStr>, ctor_arity: &mut HashMap<InternedStr, uint>) {
+    for data in module.dataDefinitions.iter() {
+        for ctor in data.constructors.iter() {
+            ctor_arity.insert(ctor.name.clone(), ctor.arity);
         }
-    }).collect();
-    
-    let instances2: Vec<Instance<Name>> = instances.move_iter().map(|instance| {
-        let Instance {
-            bindings : bindings,
-            constraints : constraints,
-            typ : typ,
-            classname : classname
-        } = instance;
-        Instance {
-            bindings : FromVec::<Binding<Name>>::from_vec(bindings.move_iter().map(|b| renamer.rename_binding(b)).collect()),
-            constraints : constraints,
-            typ : typ,
-            classname : classname
+    }
+}
+
+//Allocates a fresh, globally unique `Name` for every top-level binding, data
+//constructor and class method declared in `module`, adding them to `globals`
+//keyed by (module name, symbol) so same-named exports from different modules
+//don't collide, and to `globals_by_name` so an unqualified reference written in
+//a different module can still fall back to finding it. Shared between
+//`rename_module` (one module's own exports) and `rename_modules` (every
+//module's exports, before any bodies are renamed).
+fn collect_global_env(module: &Module<InternedStr>, globals: &mut HashMap<(InternedStr, InternedStr), Name>, globals_by_name: &mut HashMap<InternedStr, Name>, unique_id: &mut uint) {
+    let module_name = module.name.clone();
+    let mut declare = |name: InternedStr| {
+        *unique_id += 1;
+        let n = Name { name: name.clone(), uid: *unique_id, module: None };
+        globals_by_name.insert(name.clone(), n.clone());
+        let key = (module_name.clone(), name);
+        globals.insert(key, n);
+    };
+    for bind in module.bindings.iter() {
+        declare(bind.name.clone());
+    }
+    for data in module.dataDefinitions.iter() {
+        for ctor in data.constructors.iter() {
+            declare(ctor.name.clone());
         }
-    }).collect();
-    
-    let bindings2 : Vec<Binding<Name>> = bindings.move_iter().map(|b| renamer.rename_binding(b)).collect();
-    
-    Module {
-        name: renamer.make_unique(name),
-        classes : classes,
-        dataDefinitions: FromVec::from_vec(data_definitions2),
-        typeDeclarations: typeDeclarations,
-        bindings : FromVec::from_vec(bindings2),
-        instances: FromVec::from_vec(instances2)
     }
+    for class in module.classes.iter() {
+        for decl in class.declarations.iter() {
+            declare(decl.name.clone());
+        }
+    }
+}
+
+//Renames a single, module-less expression, e.g. for a REPL or a standalone test
+//snippet. There's no enclosing module to allocate real uids from, so any name
+//in `known_globals` resolves the way the original renamer treated every global:
+//by name, with `uid: 0`. Anything else free in `expr` is a genuine unbound
+//variable and is reported rather than silently accepted.
+pub fn rename_expr(expr: TypedExpr<InternedStr>, known_globals: &[InternedStr]) -> Result<(TypedExpr<Name>, Vec<ShadowWarning>), Vec<RenameError>> {
+    let mut globals_by_name = HashMap::new();
+    for name in known_globals.iter() {
+        globals_by_name.insert(name.clone(), Name { name: name.clone(), uid: 0, module: None });
+    }
+    let mut renamer = Renamer {
+        uniques: ScopedMap::new(),
+        unique_id: 1,
+        globals: HashMap::new(),
+        globals_by_name: globals_by_name,
+        module_name: intern(""),
+        ctor_arity: HashMap::new(),
+        imports: HashSet::new(),
+        errors: Vec::new(),
+        warnings: Vec::new()
+    };
+    let e = renamer.rename(expr);
+    if renamer.errors.is_empty() { Ok((e, renamer.warnings)) } else { Err(renamer.errors) }
 }
 
+pub fn rename_module(module: Module<InternedStr>, imports: &[InternedStr]) -> Result<(Module<Name>, Vec<ShadowWarning>), Vec<RenameError>> {
+    let mut unique_id = 1;
+    let mut globals = HashMap::new();
+    let mut globals_by_name = HashMap::new();
+    collect_global_env(&module, &mut globals, &mut globals_by_name, &mut unique_id);
+    let module_name = module.name.clone();
+
+    let mut renamer = Renamer {
+        uniques: ScopedMap::new(),
+        unique_id: unique_id,
+        globals: globals,
+        globals_by_name: globals_by_name,
+        module_name: module_name,
+        ctor_arity: HashMap::new(),
+        imports: imports.iter().map(|i| i.clone()).collect(),
+        errors: Vec::new(),
+        warnings: Vec::new()
+    };
+    let renamed = renamer.rename_module_body(module);
+
+    if renamer.errors.is_empty() { Ok((renamed, renamer.warnings)) } else { Err(renamer.errors) }
+}
+
+//Renames every module in a program together so cross-module references resolve
+//to their real uid, whether written qualified or not. All modules' exports and
+//constructor arities are collected up front, before any body is renamed, so a
+//`ConstructorPattern` is resolved and arity-checked the same way whether it's
+//matched in its own module or an importer's. Each module is then renamed
+//against that shared environment with its own per-module `ScopedMap` layered on
+//top for locals.
+pub fn rename_modules(modules: ~[Module<InternedStr>]) -> Result<(~[Module<Name>], Vec<ShadowWarning>), Vec<RenameError>> {
+    let mut unique_id = 1;
+    let mut globals = HashMap::new();
+    let mut globals_by_name = HashMap::new();
+    let mut ctor_arity = HashMap::new();
+    for module in modules.iter() {
+        collect_global_env(module, &mut globals, &mut globals_by_name, &mut unique_id);
+        collect_ctor_arity(module, &mut ctor_arity);
+    }
+    let imports: HashSet<InternedStr> = modules.iter().map(|m| m.name.clone()).collect();
+
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let renamed: Vec<Module<Name>> = modules.move_iter().map(|module| {
+        let module_name = module.name.clone();
+        let mut renamer = Renamer {
+            uniques: ScopedMap::new(),
+            unique_id: unique_id,
+            globals: globals.clone(),
+            globals_by_name: globals_by_name.clone(),
+            module_name: module_name,
+            ctor_arity: ctor_arity.clone(),
+            imports: imports.clone(),
+            errors: Vec::new(),
+            warnings: Vec::new()
+        };
+        let renamed_module = renamer.rename_module_body(module);
+        unique_id = renamer.unique_id;
+        errors.extend(renamer.errors.move_iter());
+        warnings.extend(renamer.warnings.move_iter());
+        renamed_module
+    }).collect();
+
+    if errors.is_empty() { Ok((FromVec::from_vec(renamed), warnings)) } else { Err(errors) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{qualifier, ctor_key, resolve_global, Name};
+    use interner::intern;
+    use std::collections::HashMap;
+
+    #[test]
+    fn qualifier_splits_module_and_symbol() {
+        let (module, symbol) = qualifier(&intern("Data.List.foldr")).unwrap();
+        assert_eq!(module.as_slice(), "Data.List");
+        assert_eq!(symbol.as_slice(), "foldr");
+    }
+
+    #[test]
+    fn qualifier_rejects_unqualified_and_operator_names() {
+        assert!(qualifier(&intern("foldr")).is_none());
+        //The compose operator, not a qualified reference into module `.`.
+        assert!(qualifier(&intern(".")).is_none());
+        //Lowercase-led segment before the last dot isn't a module name.
+        assert!(qualifier(&intern("foo.bar")).is_none());
+    }
+
+    #[test]
+    fn ctor_key_strips_qualifier() {
+        assert_eq!(ctor_key(&intern("Data.Maybe.Just")).as_slice(), "Just");
+        assert_eq!(ctor_key(&intern("Just")).as_slice(), "Just");
+    }
+
+    #[test]
+    fn resolve_global_prefers_current_module_then_falls_back_to_others() {
+        let mut globals = HashMap::new();
+        let mut globals_by_name = HashMap::new();
+        let prelude = intern("Prelude");
+        let main = intern("Main");
+        let map_name = intern("map");
+        let resolved_map = Name { name: map_name.clone(), uid: 7, module: None };
+        globals.insert((prelude.clone(), map_name.clone()), resolved_map.clone());
+        globals_by_name.insert(map_name.clone(), resolved_map);
+
+        //`Main` never declares `map` itself, so an unqualified use of it falls
+        //back to the one `Prelude` exports instead of failing to resolve.
+        let found = resolve_global(&main, &map_name, &globals, &globals_by_name).unwrap();
+        assert_eq!(found.uid, 7);
+
+        //Once `Main` declares its own `map`, it wins over the fallback by name.
+        let main_map = Name { name: map_name.clone(), uid: 3, module: None };
+        globals.insert((main.clone(), map_name.clone()), main_map);
+        assert_eq!(resolve_global(&main, &map_name, &globals, &globals_by_name).unwrap().uid, 3);
+
+        //A symbol nobody declares still fails to resolve.
+        assert!(resolve_global(&main, &intern("nope"), &globals, &globals_by_name).is_none());
+    }
+}
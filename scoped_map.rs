@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+//A map whose bindings are organized into a stack of scopes: `insert` always
+//writes to the innermost scope, `find` searches from innermost to outermost,
+//and `exit_scope` discards everything bound since the matching `enter_scope`.
+pub struct ScopedMap<K, V> {
+    scopes: Vec<HashMap<K, V>>
+}
+
+impl<K: Eq + Hash + Clone, V> ScopedMap<K, V> {
+    pub fn new() -> ScopedMap<K, V> {
+        ScopedMap { scopes: vec![HashMap::new()] }
+    }
+
+    pub fn enter_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn exit_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    pub fn insert(&mut self, k: K, v: V) -> bool {
+        self.scopes.mut_last().unwrap().insert(k, v)
+    }
+
+    pub fn find<'a>(&'a self, k: &K) -> Option<&'a V> {
+        for scope in self.scopes.iter().rev() {
+            match scope.find(k) {
+                Some(v) => return Some(v),
+                None => ()
+            }
+        }
+        None
+    }
+
+    //True if `k` is already bound in some enclosing scope, i.e. any scope other
+    //than the innermost one. Lets callers tell a shadowing bind (same name, outer
+    //scope) apart from a plain rebind (same name, same scope).
+    pub fn in_outer_scope(&self, k: &K) -> bool {
+        let mut scopes = self.scopes.iter().rev();
+        scopes.next();
+        scopes.any(|scope| scope.contains_key(k))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScopedMap;
+
+    #[test]
+    fn find_searches_outward_through_enclosing_scopes() {
+        let mut m: ScopedMap<&str, int> = ScopedMap::new();
+        m.insert("x", 1);
+        m.enter_scope();
+        assert_eq!(m.find(&"x"), Some(&1));
+        m.insert("x", 2);
+        assert_eq!(m.find(&"x"), Some(&2));
+        m.exit_scope();
+        assert_eq!(m.find(&"x"), Some(&1));
+    }
+
+    #[test]
+    fn in_outer_scope_tells_shadowing_apart_from_a_same_scope_rebind() {
+        let mut m: ScopedMap<&str, int> = ScopedMap::new();
+        m.insert("x", 1);
+        m.enter_scope();
+        //`x` is bound in the enclosing scope, so rebinding it here shadows it.
+        assert!(m.in_outer_scope(&"x"));
+        m.insert("y", 2);
+        //`y` was just bound in this same scope, not an enclosing one.
+        assert!(!m.in_outer_scope(&"y"));
+    }
+}